@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::RwLock;
+
+use prometheus_client::encoding::EncodeLabelValue;
+use prometheus_client::metrics::counter::Counter;
+
+/// Whether a label set was admitted as-is or routed to the overflow series.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum Overflow {
+    False,
+    True,
+}
+
+/// Caps the number of distinct label sets a `Family` is allowed to grow to.
+pub struct CardinalityGuard<L> {
+    seen: RwLock<HashSet<L>>,
+    max_label_sets: usize,
+    dropped: Counter,
+}
+
+pub enum Admission<L> {
+    Admit(L),
+    Overflow,
+}
+
+impl<L: Clone + Eq + Hash> CardinalityGuard<L> {
+    pub fn new(max_label_sets: usize, dropped: Counter) -> Self {
+        Self {
+            seen: RwLock::new(HashSet::new()),
+            max_label_sets,
+            dropped,
+        }
+    }
+
+    /// Admit `labels` if it's already known or there's room for one more
+    /// distinct label set; otherwise count it as dropped and signal overflow.
+    pub fn admit(&self, labels: L) -> Admission<L> {
+        if self
+            .seen
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&labels)
+        {
+            return Admission::Admit(labels);
+        }
+
+        let mut seen = self.seen.write().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(&labels) {
+            return Admission::Admit(labels);
+        }
+        if seen.len() >= self.max_label_sets {
+            self.dropped.inc();
+            return Admission::Overflow;
+        }
+        seen.insert(labels.clone());
+        Admission::Admit(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_admit<L>(admission: &Admission<L>) -> bool {
+        matches!(admission, Admission::Admit(_))
+    }
+
+    #[test]
+    fn admits_distinct_labels_up_to_the_limit() {
+        let guard = CardinalityGuard::new(2, Counter::default());
+        assert!(is_admit(&guard.admit("a")));
+        assert!(is_admit(&guard.admit("b")));
+    }
+
+    #[test]
+    fn reuses_admission_for_an_already_seen_label() {
+        let guard = CardinalityGuard::new(1, Counter::default());
+        assert!(is_admit(&guard.admit("a")));
+        assert!(is_admit(&guard.admit("a")));
+    }
+
+    #[test]
+    fn overflows_once_the_limit_is_reached() {
+        let guard = CardinalityGuard::new(1, Counter::default());
+        assert!(is_admit(&guard.admit("a")));
+        assert!(matches!(guard.admit("b"), Admission::Overflow));
+        assert_eq!(guard.dropped.get(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+
+use prometheus_client::metrics::family::MetricConstructor;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use serde::Deserialize;
+
+/// Unit a histogram records in; only affects the suffix its metric is
+/// registered under (e.g. `latency` + `seconds` -> `latency_seconds`).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistogramUnit {
+    Count,
+    Bytes,
+    Seconds,
+}
+
+impl HistogramUnit {
+    fn suffix(self) -> &'static str {
+        match self {
+            HistogramUnit::Count => "",
+            HistogramUnit::Bytes => "_bytes",
+            HistogramUnit::Seconds => "_seconds",
+        }
+    }
+}
+
+/// Bucket layout for a configured histogram.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BucketLayout {
+    Exponential {
+        start: f64,
+        factor: f64,
+        count: u16,
+    },
+    /// Geometrically spaced across decades `lo..=hi`, linearly subdivided
+    /// into `k` edges within each decade: `10^d * (1 + i/k)` for `i in 0..k`.
+    LogLinear { lo: i32, hi: i32, k: usize },
+}
+
+/// Upper bound on `k` in a `LogLinear` layout, past which a single histogram
+/// would carry an unreasonable number of buckets.
+const MAX_LOG_LINEAR_K: usize = 1_000;
+
+impl BucketLayout {
+    fn validate(&self) -> Result<(), String> {
+        if let BucketLayout::LogLinear { lo, hi, k } = self {
+            if hi < lo {
+                return Err(format!("log_linear layout has hi ({hi}) < lo ({lo})"));
+            }
+            if *k == 0 {
+                return Err("log_linear layout has k = 0, which produces no buckets".to_string());
+            }
+            if *k > MAX_LOG_LINEAR_K {
+                return Err(format!(
+                    "log_linear layout has k ({k}) > the maximum of {MAX_LOG_LINEAR_K}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn buckets(&self) -> Vec<f64> {
+        match self {
+            BucketLayout::Exponential {
+                start,
+                factor,
+                count,
+            } => exponential_buckets(*start, *factor, *count).collect(),
+            BucketLayout::LogLinear { lo, hi, k } => {
+                let mut edges = Vec::with_capacity((*hi - *lo + 1) as usize * k);
+                for d in *lo..=*hi {
+                    let decade = 10f64.powi(d);
+                    for i in 0..*k {
+                        edges.push(decade * (1.0 + i as f64 / *k as f64));
+                    }
+                }
+                edges
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistogramConfig {
+    pub name: String,
+    pub unit: HistogramUnit,
+    pub layout: BucketLayout,
+}
+
+impl HistogramConfig {
+    /// Metric name this histogram should be registered under, unit suffix included.
+    pub fn metric_name(&self) -> String {
+        format!("{}{}", self.name, self.unit.suffix())
+    }
+
+    pub fn build(&self) -> Histogram {
+        Histogram::new(self.layout.buckets().into_iter())
+    }
+}
+
+impl MetricConstructor<Histogram> for HistogramConfig {
+    fn new_metric(&self) -> Histogram {
+        self.build()
+    }
+}
+
+/// Background connectivity prober settings. An empty `targets` list disables the prober.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProbeConfig {
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default = "default_probe_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            interval_secs: default_probe_interval_secs(),
+        }
+    }
+}
+
+fn default_probe_interval_secs() -> u64 {
+    15
+}
+
+/// Cap on distinct label sets per guarded metric family.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CardinalityConfig {
+    #[serde(default = "default_max_label_sets")]
+    pub max_label_sets: usize,
+}
+
+impl Default for CardinalityConfig {
+    fn default() -> Self {
+        Self {
+            max_label_sets: default_max_label_sets(),
+        }
+    }
+}
+
+fn default_max_label_sets() -> usize {
+    10_000
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub histograms: Vec<HistogramConfig>,
+    #[serde(default)]
+    pub probe: ProbeConfig,
+    #[serde(default)]
+    pub cardinality: CardinalityConfig,
+}
+
+impl Config {
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let raw = fs::read_to_string(path)?;
+        let config: Config = toml::from_str(&raw)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        for histogram in &config.histograms {
+            histogram
+                .layout
+                .validate()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        }
+        Ok(config)
+    }
+
+    pub fn histogram(&self, name: &str) -> Option<&HistogramConfig> {
+        self.histograms.iter().find(|h| h.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_linear_buckets_cover_every_decade() {
+        let layout = BucketLayout::LogLinear { lo: 0, hi: 1, k: 2 };
+        assert_eq!(layout.buckets(), vec![1.0, 1.5, 10.0, 15.0]);
+    }
+
+    #[test]
+    fn log_linear_rejects_hi_less_than_lo() {
+        let layout = BucketLayout::LogLinear { lo: 1, hi: 0, k: 2 };
+        assert!(layout.validate().is_err());
+    }
+
+    #[test]
+    fn log_linear_rejects_zero_k() {
+        let layout = BucketLayout::LogLinear { lo: 0, hi: 1, k: 0 };
+        assert!(layout.validate().is_err());
+    }
+
+    #[test]
+    fn log_linear_rejects_unreasonably_large_k() {
+        let layout = BucketLayout::LogLinear {
+            lo: 0,
+            hi: 1,
+            k: MAX_LOG_LINEAR_K + 1,
+        };
+        assert!(layout.validate().is_err());
+    }
+
+    #[test]
+    fn exponential_layout_accepts_validation() {
+        let layout = BucketLayout::Exponential {
+            start: 0.1,
+            factor: 2.0,
+            count: 5,
+        };
+        assert!(layout.validate().is_ok());
+        assert_eq!(layout.buckets().len(), 5);
+    }
+}
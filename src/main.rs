@@ -1,14 +1,26 @@
-use std::sync::Mutex;
+use std::time::Instant;
 
 use actix_web::{web, App, HttpResponse, HttpServer, Responder, Result};
 use prometheus_client::encoding::text::encode;
 use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
 use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
-use prometheus_client::registry::Registry;
 use serde::{Deserialize, Serialize};
 
+mod cardinality;
+mod config;
+mod middleware;
+mod probe;
+mod registry;
+
+use cardinality::{Admission, CardinalityGuard, Overflow};
+use config::{Config, HistogramConfig};
+use middleware::HttpMetrics;
+use probe::ProbeMetrics;
+use registry::{default_registry, register_metric};
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
 pub enum Method {
     Get,
@@ -21,10 +33,12 @@ pub struct AppLabels {
     pub script_name: String,
     pub namespace: String,
     pub app: String,
+    pub overflow: Overflow,
 }
 
 pub struct CountMetrics {
     requests: Family<AppLabels, Counter>,
+    guard: CardinalityGuard<AppLabels>,
 }
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct LatencyLabels {
@@ -32,6 +46,7 @@ pub struct LatencyLabels {
     pub r#type: String,
     pub module: String,
     pub status: i8,
+    pub overflow: Overflow,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,25 +57,42 @@ pub struct LatencyInfo {
     pub status: i8,
 }
 pub struct HisgMetrics {
-    requests_hig: Family<LatencyLabels, Histogram>,
+    requests_hig: Family<LatencyLabels, Histogram, HistogramConfig>,
+    guard: CardinalityGuard<LatencyLabels>,
 }
 
 impl HisgMetrics {
     pub fn hisg_request(&self, ll: &LatencyLabels, d: f64) {
-        self.requests_hig.get_or_create(ll).observe(d);
+        let ll = match self.guard.admit(ll.clone()) {
+            Admission::Admit(ll) => ll,
+            Admission::Overflow => LatencyLabels {
+                method: ll.method.clone(),
+                r#type: "overflow".to_string(),
+                module: "overflow".to_string(),
+                status: 0,
+                overflow: Overflow::True,
+            },
+        };
+        self.requests_hig.get_or_create(&ll).observe(d);
     }
 }
 
 impl CountMetrics {
     pub fn inc_requests(&self, app_labels: &AppLabels) {
-        self.requests.get_or_create(app_labels).inc();
+        let app_labels = match self.guard.admit(app_labels.clone()) {
+            Admission::Admit(app_labels) => app_labels,
+            Admission::Overflow => AppLabels {
+                method: app_labels.method.clone(),
+                script_name: "overflow".to_string(),
+                namespace: "overflow".to_string(),
+                app: "overflow".to_string(),
+                overflow: Overflow::True,
+            },
+        };
+        self.requests.get_or_create(&app_labels).inc();
     }
 }
 
-pub struct AppState {
-    pub registry: Registry,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppInfo {
     pub script_name: String,
@@ -68,10 +100,39 @@ pub struct AppInfo {
     pub app: String,
 }
 
-pub async fn metrics_handler(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse> {
-    let state = state.lock().unwrap();
+/// Self-instrumentation for the `/metrics` endpoint itself, so operators can
+/// alert on scrape cost once label cardinality grows.
+pub struct ScrapeMetrics {
+    response_size: Gauge,
+    duration_seconds: Histogram,
+}
+
+impl ScrapeMetrics {
+    pub fn new() -> Self {
+        Self {
+            response_size: Gauge::default(),
+            duration_seconds: Histogram::new(exponential_buckets(0.001, 2.0, 10)),
+        }
+    }
+}
+
+impl Default for ScrapeMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn metrics_handler(metrics: web::Data<ScrapeMetrics>) -> Result<HttpResponse> {
+    let start = Instant::now();
     let mut body = String::new();
-    encode(&mut body, &state.registry).unwrap();
+    {
+        let registry = default_registry().read().unwrap_or_else(|e| e.into_inner());
+        encode(&mut body, &registry).unwrap();
+    }
+    metrics.response_size.set(body.len() as i64);
+    metrics
+        .duration_seconds
+        .observe(start.elapsed().as_secs_f64());
     Ok(HttpResponse::Ok()
         .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
         .body(body))
@@ -83,6 +144,7 @@ pub async fn test_handler(metrics: web::Data<CountMetrics>) -> impl Responder {
         namespace: "test".to_string(),
         script_name: "test-script".to_string(),
         app: "test".to_string(),
+        overflow: Overflow::False,
     };
     metrics.inc_requests(&al);
     "okay".to_string()
@@ -97,6 +159,7 @@ pub async fn script_handler(
         namespace: body.namespace.clone(),
         script_name: body.script_name.clone(),
         app: body.app.clone(),
+        overflow: Overflow::False,
     };
     metrics.inc_requests(&al);
     "post_okay".to_string()
@@ -111,6 +174,7 @@ pub async fn duration_handler(
         r#type: body.r#type.clone(),
         module: body.module.clone(),
         status: body.status,
+        overflow: Overflow::False,
     };
     metrics.hisg_request(&ll, body.duration as f64);
     "post_latency_okay".to_string()
@@ -118,32 +182,70 @@ pub async fn duration_handler(
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let config = Config::from_file("config.toml").expect("failed to read config.toml");
+    let latency_config = config
+        .histogram("latency")
+        .expect("config.toml is missing a [[histograms]] entry named \"latency\"")
+        .clone();
+
+    let dropped_metrics = Counter::default();
+    register_metric(
+        "metrics_dropped",
+        "Count of label sets rejected after a metric family hit its cardinality limit",
+        dropped_metrics.clone(),
+    );
+
     let metrics = web::Data::new(CountMetrics {
         requests: Family::default(),
+        guard: CardinalityGuard::new(config.cardinality.max_label_sets, dropped_metrics.clone()),
     });
     let latency_metrics = web::Data::new(HisgMetrics {
-        requests_hig: Family::<LatencyLabels, Histogram>::new_with_constructor(|| {
-            Histogram::new(exponential_buckets(10.0, 5.0, 5))
-        }),
+        requests_hig: Family::<LatencyLabels, Histogram, HistogramConfig>::new_with_constructor(
+            latency_config,
+        ),
+        guard: CardinalityGuard::new(config.cardinality.max_label_sets, dropped_metrics),
     });
-    let mut state = AppState {
-        registry: Registry::default(),
-    };
-    state
-        .registry
-        .register("requests", "Count of requests", metrics.requests.clone());
-    state.registry.register(
-        "latency",
+    register_metric("requests", "Count of requests", metrics.requests.clone());
+    register_metric(
+        &config.histogram("latency").unwrap().metric_name(),
         "Record latency",
         latency_metrics.requests_hig.clone(),
     );
-    let state = web::Data::new(Mutex::new(state));
+
+    let http_metrics = HttpMetrics::new();
+    register_metric(
+        "http_requests",
+        "Count of requests per route, auto-instrumented by middleware",
+        http_metrics.requests_total.clone(),
+    );
+    register_metric(
+        "http_request_duration_seconds",
+        "Request duration per route, auto-instrumented by middleware",
+        http_metrics.request_duration_seconds.clone(),
+    );
+
+    let probe_metrics = ProbeMetrics::new();
+    probe_metrics.register();
+    probe::spawn(config.probe.clone(), probe_metrics);
+
+    let scrape_metrics = web::Data::new(ScrapeMetrics::new());
+    register_metric(
+        "scrape_response_size_bytes",
+        "Size of the encoded /metrics response body",
+        scrape_metrics.response_size.clone(),
+    );
+    register_metric(
+        "scrape_duration_seconds",
+        "Time spent encoding the /metrics response body",
+        scrape_metrics.duration_seconds.clone(),
+    );
 
     HttpServer::new(move || {
         App::new()
+            .wrap(http_metrics.middleware())
             .app_data(metrics.clone())
             .app_data(latency_metrics.clone())
-            .app_data(state.clone())
+            .app_data(scrape_metrics.clone())
             .service(web::resource("/metrics").route(web::get().to(metrics_handler)))
             .service(web::resource("/test_handler").route(web::get().to(test_handler)))
             .service(web::resource("/script_handler").route(web::post().to(script_handler)))
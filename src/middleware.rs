@@ -0,0 +1,120 @@
+use std::future::{ready, Ready};
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+
+/// Labels every route wrapped by [`HttpMetrics`] is instrumented with.
+/// `route` is the matched route pattern (e.g. `/script_handler`), not the raw path.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct HttpLabels {
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+}
+
+/// Request counter and latency histogram shared by every route in the `App`.
+/// Cheap to clone: each `Family` is reference-counted internally.
+#[derive(Clone)]
+pub struct HttpMetrics {
+    pub requests_total: Family<HttpLabels, Counter>,
+    pub request_duration_seconds: Family<HttpLabels, Histogram>,
+}
+
+impl HttpMetrics {
+    pub fn new() -> Self {
+        Self {
+            requests_total: Family::default(),
+            request_duration_seconds: Family::<HttpLabels, Histogram>::new_with_constructor(
+                || Histogram::new(exponential_buckets(0.005, 2.0, 10)),
+            ),
+        }
+    }
+
+    /// Wrap an `App` with this registry's auto-instrumentation middleware.
+    pub fn middleware(&self) -> HttpMetricsMiddleware {
+        HttpMetricsMiddleware {
+            metrics: self.clone(),
+        }
+    }
+}
+
+impl Default for HttpMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct HttpMetricsMiddleware {
+    metrics: HttpMetrics,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HttpMetricsMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = HttpMetricsService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpMetricsService {
+            service,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+pub struct HttpMetricsService<S> {
+    service: S,
+    metrics: HttpMetrics,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpMetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().as_str().to_string();
+        let metrics = self.metrics.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            let elapsed = start.elapsed().as_secs_f64();
+            let route = res
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| "unmatched".to_string());
+            let labels = HttpLabels {
+                method,
+                route,
+                status: res.status().as_u16(),
+            };
+            metrics.requests_total.get_or_create(&labels).inc();
+            metrics
+                .request_duration_seconds
+                .get_or_create(&labels)
+                .observe(elapsed);
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,164 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, ICMP};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::config::ProbeConfig;
+use crate::registry::register_metric;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum ProbeResult {
+    Success,
+    Failure,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TargetLabels {
+    pub target: String,
+    pub result: ProbeResult,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TargetRttLabels {
+    pub target: String,
+}
+
+/// Counters and RTT histogram shared by every target the prober checks.
+#[derive(Clone)]
+pub struct ProbeMetrics {
+    probes_total: Family<TargetLabels, Counter>,
+    probe_rtt_seconds: Family<TargetRttLabels, Histogram>,
+}
+
+impl ProbeMetrics {
+    pub fn new() -> Self {
+        Self {
+            probes_total: Family::default(),
+            probe_rtt_seconds: Family::<TargetRttLabels, Histogram>::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.001, 2.0, 12))
+            }),
+        }
+    }
+
+    pub fn register(&self) {
+        register_metric(
+            "probes",
+            "Count of connectivity probes per target and result",
+            self.probes_total.clone(),
+        );
+        register_metric(
+            "probe_rtt_seconds",
+            "Round-trip time of successful connectivity probes",
+            self.probe_rtt_seconds.clone(),
+        );
+    }
+}
+
+/// ICMP sockets for each address family, created once and reused across every
+/// tick and target instead of opening a socket per probe.
+struct IcmpClients {
+    v4: Client,
+    v6: Client,
+}
+
+impl IcmpClients {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            v4: Client::new(&Config::default())?,
+            v6: Client::new(&Config::builder().kind(ICMP::V6).build())?,
+        })
+    }
+
+    fn pick(&self, addr: IpAddr) -> &Client {
+        match addr {
+            IpAddr::V4(_) => &self.v4,
+            IpAddr::V6(_) => &self.v6,
+        }
+    }
+}
+
+/// Spawn the background prober described by `config`. A no-op if no targets are configured.
+pub fn spawn(config: ProbeConfig, metrics: ProbeMetrics) {
+    if config.targets.is_empty() {
+        return;
+    }
+
+    // `None` if raw sockets aren't available (e.g. unprivileged containers),
+    // in which case every probe falls back to the TCP connect check below.
+    let icmp_clients = IcmpClients::new().ok();
+
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(config.interval_secs));
+        loop {
+            tick.tick().await;
+            for target in &config.targets {
+                let (result, rtt) = probe_once(icmp_clients.as_ref(), target).await;
+                metrics
+                    .probes_total
+                    .get_or_create(&TargetLabels {
+                        target: target.clone(),
+                        result,
+                    })
+                    .inc();
+                if let Some(rtt) = rtt {
+                    metrics
+                        .probe_rtt_seconds
+                        .get_or_create(&TargetRttLabels {
+                            target: target.clone(),
+                        })
+                        .observe(rtt);
+                }
+            }
+        }
+    });
+}
+
+/// Probe a single target: ICMP echo first, falling back to a TCP connect
+/// for environments without `CAP_NET_RAW` (e.g. unprivileged containers).
+async fn probe_once(icmp_clients: Option<&IcmpClients>, target: &str) -> (ProbeResult, Option<f64>) {
+    let addr: IpAddr = match target.parse() {
+        Ok(addr) => addr,
+        Err(_) => return (ProbeResult::Failure, None),
+    };
+
+    if let Some(clients) = icmp_clients {
+        let start = Instant::now();
+        if icmp_ping(clients, addr).await.is_ok() {
+            return (ProbeResult::Success, Some(start.elapsed().as_secs_f64()));
+        }
+    }
+
+    let start = Instant::now();
+    match tcp_connect_probe(addr).await {
+        Ok(()) => (ProbeResult::Success, Some(start.elapsed().as_secs_f64())),
+        Err(_) => (ProbeResult::Failure, None),
+    }
+}
+
+async fn icmp_ping(clients: &IcmpClients, addr: IpAddr) -> std::io::Result<()> {
+    let mut pinger = clients
+        .pick(addr)
+        .pinger(addr, PingIdentifier(std::process::id() as u16))
+        .await;
+    pinger
+        .ping(PingSequence(0), &[0; 8])
+        .await
+        .map(|_| ())
+        .map_err(std::io::Error::other)
+}
+
+async fn tcp_connect_probe(addr: IpAddr) -> std::io::Result<()> {
+    timeout(
+        Duration::from_secs(2),
+        TcpStream::connect(SocketAddr::new(addr, 443)),
+    )
+    .await
+    .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out"))??;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+use prometheus_client::encoding::EncodeMetric;
+use prometheus_client::registry::Registry;
+
+/// Process-wide metric registry.
+///
+/// Scrapes only ever need a read lock, so concurrent `/metrics` requests no
+/// longer serialize against each other the way a single `Mutex<AppState>`
+/// did. Any module can add a metric family with [`register_metric`] without
+/// the registry being threaded through `web::Data`; families do not register
+/// themselves when constructed, each is added by an explicit call.
+static REGISTRY: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(Registry::default()));
+
+/// Access the process-wide registry.
+pub fn default_registry() -> &'static RwLock<Registry> {
+    &REGISTRY
+}
+
+/// Register a metric family under `name` with the process-wide registry.
+pub fn register_metric(
+    name: &str,
+    help: &str,
+    metric: impl EncodeMetric + Send + Sync + std::fmt::Debug + 'static,
+) {
+    default_registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .register(name, help, metric);
+}